@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use alita::request::FetchRequest;
+use anyhow::Result;
+use reqwest::Response;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Notify;
+use tracing::debug;
+
+const DEFAULT_TTL_SECS: u64 = 300;
+
+/// A cached response body plus the bits of the original response we need to decide whether
+/// it's still fresh, or to revalidate it with a conditional request when it isn't.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub html: String,
+    pub fetched_at: u64,
+    pub max_age: Option<u64>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl CacheEntry {
+    pub fn new(html: String, headers: CacheHeaders) -> Self {
+        CacheEntry {
+            html,
+            fetched_at: now_secs(),
+            max_age: headers.max_age,
+            etag: headers.etag,
+            last_modified: headers.last_modified,
+        }
+    }
+
+    fn refreshed(&self, headers: CacheHeaders) -> Self {
+        CacheEntry {
+            html: self.html.clone(),
+            fetched_at: now_secs(),
+            max_age: headers.max_age.or(self.max_age),
+            etag: headers.etag.or_else(|| self.etag.clone()),
+            last_modified: headers.last_modified.or_else(|| self.last_modified.clone()),
+        }
+    }
+}
+
+/// The subset of response headers that decide cache freshness, captured from a `reqwest`
+/// response so they can be stored alongside the body.
+#[derive(Debug)]
+pub struct CacheHeaders {
+    pub max_age: Option<u64>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// Whether `Cache-Control` allows storing this response at all. `false` for `no-store` and
+    /// `private` (we're a shared cache serving every caller, so `private` isn't safe to keep).
+    pub cacheable: bool,
+}
+
+impl Default for CacheHeaders {
+    fn default() -> Self {
+        CacheHeaders {
+            max_age: None,
+            etag: None,
+            last_modified: None,
+            cacheable: true,
+        }
+    }
+}
+
+impl CacheHeaders {
+    pub fn from_response(res: &Response) -> Self {
+        let headers = res.headers();
+        let cache_control = headers.get(reqwest::header::CACHE_CONTROL).and_then(|v| v.to_str().ok());
+
+        let directives: Vec<&str> = cache_control
+            .map(|v| v.split(',').map(str::trim).collect())
+            .unwrap_or_default();
+        let no_store = directives.iter().any(|d| d.eq_ignore_ascii_case("no-store"));
+        let private = directives.iter().any(|d| d.eq_ignore_ascii_case("private"));
+        // no-cache doesn't mean "don't store", it means "always revalidate before serving" -
+        // model that as an already-stale entry so `is_fresh` forces a conditional request.
+        let no_cache = directives.iter().any(|d| d.eq_ignore_ascii_case("no-cache"));
+
+        let max_age = if no_cache {
+            Some(0)
+        } else {
+            cache_control.and_then(parse_max_age).or_else(|| {
+                headers
+                    .get(reqwest::header::EXPIRES)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(parse_expires)
+            })
+        };
+
+        let etag = headers
+            .get(reqwest::header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        let last_modified = headers
+            .get(reqwest::header::LAST_MODIFIED)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        CacheHeaders {
+            max_age,
+            etag,
+            last_modified,
+            cacheable: !no_store && !private,
+        }
+    }
+}
+
+/// Disk-backed cache for rendered HTML, keyed by a hash of the URL and the fetch knobs that
+/// affect the output. Entries live under `ALITA_CACHE_DIR` (default `temp_dir()/alita-cache`)
+/// as one file per key.
+pub struct ResponseCache {
+    dir: PathBuf,
+    default_ttl: Duration,
+    pending: Mutex<HashMap<String, Arc<Notify>>>,
+}
+
+impl ResponseCache {
+    pub fn new() -> Self {
+        let dir = env::var("ALITA_CACHE_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| env::temp_dir().join("alita-cache"));
+        std::fs::create_dir_all(&dir).ok();
+
+        let default_ttl = env::var("ALITA_CACHE_DEFAULT_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(DEFAULT_TTL_SECS));
+
+        ResponseCache {
+            dir,
+            default_ttl,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Hashes the normalized url plus every `FetchRequest` knob that can change the rendered
+    /// output into a stable cache key - two requests only share a cache entry if they'd produce
+    /// the same html. `session_id` is folded in too so a cache bug can't accidentally serve one
+    /// session's (personalized) html to another caller - callers with a `session_id` set should
+    /// bypass the cache outright, but this is cheap insurance if that ever changes.
+    pub fn key_for(req: &FetchRequest) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(normalize_url(&req.url).as_bytes());
+        hasher.update(b"\0");
+        hasher.update(req.is_blocked_elements.join(",").as_bytes());
+        hasher.update(b"\0");
+        hasher.update(req.wait_for_element.as_deref().unwrap_or("").as_bytes());
+        hasher.update(b"\0");
+        hasher.update(req.proxy.as_deref().unwrap_or("").as_bytes());
+        hasher.update(b"\0");
+        hasher.update(req.extra_chrome_args.join(" ").as_bytes());
+        hasher.update(b"\0");
+        let block_resource_types = req.block_resource_types.as_deref().unwrap_or(&[]);
+        hasher.update(block_resource_types.join(",").as_bytes());
+        hasher.update(b"\0");
+        hasher.update(req.session_id.as_deref().unwrap_or("").as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+
+    pub async fn get(&self, key: &str) -> Option<CacheEntry> {
+        let bytes = tokio::fs::read(self.path_for(key)).await.ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    pub fn is_fresh(&self, entry: &CacheEntry) -> bool {
+        let ttl = entry
+            .max_age
+            .map(Duration::from_secs)
+            .unwrap_or(self.default_ttl);
+        let age = now_secs().saturating_sub(entry.fetched_at);
+        age < ttl.as_secs()
+    }
+
+    pub async fn store(&self, key: &str, entry: &CacheEntry) -> Result<()> {
+        let bytes = serde_json::to_vec(entry)?;
+        tokio::fs::write(self.path_for(key), bytes).await?;
+        Ok(())
+    }
+
+    /// Re-stores `entry` with a bumped `fetched_at` and any headers returned by a 304, used
+    /// after a conditional request confirms the cached body is still good.
+    pub async fn touch(&self, key: &str, entry: &CacheEntry, headers: CacheHeaders) -> Result<()> {
+        self.store(key, &entry.refreshed(headers)).await
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        self.dir.join(key)
+    }
+
+    /// Waits until no other caller is populating `key`, then claims it for this caller so two
+    /// inflight requests for the same URL don't stampede the network/chrome. The claim is
+    /// released, waking any waiters, when the returned guard is dropped.
+    pub async fn claim(&self, key: &str) -> CacheClaim<'_> {
+        loop {
+            let notify;
+            let notified = {
+                let mut pending = self.pending.lock().unwrap();
+                match pending.get(key) {
+                    Some(existing) => {
+                        notify = existing.clone();
+                        // created while the lock is held, so a release that lands before the
+                        // await below still wakes this waiter.
+                        notify.notified()
+                    }
+                    None => {
+                        pending.insert(key.to_string(), Arc::new(Notify::new()));
+                        return CacheClaim {
+                            cache: self,
+                            key: key.to_string(),
+                        };
+                    }
+                }
+            };
+
+            debug!("Waiting on in-flight fetch for cache key {}", key);
+            notified.await;
+        }
+    }
+}
+
+pub struct CacheClaim<'a> {
+    cache: &'a ResponseCache,
+    key: String,
+}
+
+impl Drop for CacheClaim<'_> {
+    fn drop(&mut self) {
+        let mut pending = self.cache.pending.lock().unwrap();
+        if let Some(notify) = pending.remove(&self.key) {
+            notify.notify_waiters();
+        }
+    }
+}
+
+/// Normalizes a url for cache-key purposes. Only the scheme and host are case-insensitive per
+/// RFC 3986 - the path and query can be case-sensitive (e.g. `/UserProfile` vs `/userprofile`
+/// are different resources on most servers), so only those two pieces get lowercased.
+fn normalize_url(url: &str) -> String {
+    let url = url.trim_end_matches('/');
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+
+    let split_at = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (authority, suffix) = rest.split_at(split_at);
+    format!(
+        "{}://{}{}",
+        scheme.to_ascii_lowercase(),
+        authority.to_ascii_lowercase(),
+        suffix
+    )
+}
+
+fn parse_max_age(cache_control: &str) -> Option<u64> {
+    cache_control.split(',').find_map(|directive| {
+        let directive = directive.trim();
+        directive
+            .strip_prefix("max-age=")
+            .and_then(|v| v.parse::<u64>().ok())
+    })
+}
+
+fn parse_expires(expires: &str) -> Option<u64> {
+    let expires = httpdate::parse_http_date(expires).ok()?;
+    let now = SystemTime::now();
+    expires.duration_since(now).ok().map(|d| d.as_secs())
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
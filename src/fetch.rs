@@ -1,29 +1,54 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::env::{self, temp_dir};
+use std::ffi::OsStr;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+use crate::cache::{CacheEntry, CacheHeaders, ResponseCache};
+use crate::network_capture::{header_entries_to_map, NetworkCapture};
 use crate::protocol::protocol::cdp::Fetch::events::RequestPausedEvent;
 use crate::protocol::protocol::cdp::Fetch::FailRequest;
+use crate::protocol::protocol::cdp::Fetch::GetResponseBody;
+use crate::protocol::protocol::cdp::Fetch::{RequestPattern, RequestStage};
 use crate::protocol::protocol::cdp::Network::ErrorReason;
 use crate::protocol::protocol::cdp::Network::ResourceType;
-use crate::tab_manager::TabManager;
+use crate::tab_manager::{TabHandle, TabManager, REQUEST_STAGE_ONLY};
 use alita::request::FetchRequest;
 use anyhow::Result;
 use base64::Engine;
+use dashmap::DashMap;
 use deadpool::managed::Pool;
 use headless_chrome::browser::tab::RequestPausedDecision;
 use headless_chrome::browser::transport::{SessionId, Transport};
 use headless_chrome::protocol::cdp::Fetch::{FulfillRequest, HeaderEntry};
+use headless_chrome::protocol::cdp::Page::{
+    CaptureScreenshotFormatOption, GetLayoutMetrics, PrintToPdfOptions, Viewport,
+};
 use headless_chrome::Tab;
 use headless_chrome::{Browser, LaunchOptionsBuilder};
 use lazy_static::lazy_static;
-use reqwest::Client;
+use reqwest::header::{IF_MODIFIED_SINCE, IF_NONE_MATCH};
+use reqwest::{Client, Proxy, StatusCode};
 use scraper::Html;
 use tracing::{debug, info};
 
 lazy_static! {
+    // the Fetch-domain patterns a capture_network call widens a tab to for its duration, so the
+    // interceptor also sees response status/headers/bodies; see configure_interceptor.
+    static ref BOTH_STAGES: Vec<RequestPattern> = vec![
+        RequestPattern {
+            url_pattern: None,
+            resource_Type: None,
+            request_stage: Some(RequestStage::Request),
+        },
+        RequestPattern {
+            url_pattern: None,
+            resource_Type: None,
+            request_stage: Some(RequestStage::Response),
+        },
+    ];
+
     // todo: we should get these from the browser so it doesn't go out of sync,
     // but this is fine for now.
     pub static ref HEADERS: HashMap<String, String> = {
@@ -44,50 +69,272 @@ lazy_static! {
     };
 }
 
+// key used for the default client/pool, i.e. no proxy and no extra chrome args.
+const DEFAULT_POOL_KEY: &str = "";
+
+// each pool is a whole Chrome process - cap how many distinct proxy/extra_chrome_args
+// combinations can be alive at once so a caller varying these per-request can't spawn an
+// unbounded number of browsers.
+const DEFAULT_MAX_CHROME_POOLS: usize = 8;
+
 pub struct Fetch {
-    client: Client,
-    tabs: Pool<TabManager>,
+    // keyed by proxy url (or DEFAULT_POOL_KEY), so requests that share a proxy share a client.
+    clients: DashMap<String, Client>,
+    // keyed by a signature of (proxy, extra_chrome_args), since a Browser's proxy and launch
+    // flags are fixed at launch - requests with different knobs need their own browser+pool.
+    chrome_pools: DashMap<String, Arc<Pool<TabManager>>>,
+    // tracks chrome_pools' keys in least-to-most-recently-used order, so we know which pool to
+    // evict when we're over the cap.
+    pool_order: Mutex<VecDeque<String>>,
+    cache: Option<ResponseCache>,
 }
 
 impl Fetch {
     pub fn new() -> Self {
-        let user_data_dir = temp_dir().join("alita-profile");
+        let clients = DashMap::new();
+        clients.insert(
+            DEFAULT_POOL_KEY.to_string(),
+            Self::build_client(None).expect("Failed to build default reqwest client"),
+        );
+
+        let chrome_pools = DashMap::new();
+        chrome_pools.insert(
+            DEFAULT_POOL_KEY.to_string(),
+            Arc::new(Self::build_chrome_pool(None, &[]).expect("Failed to launch default browser")),
+        );
+
+        let cache = env::var("ALITA_DISABLE_CACHE")
+            .is_err()
+            .then(ResponseCache::new);
+
+        Fetch {
+            clients,
+            chrome_pools,
+            pool_order: Mutex::new(VecDeque::from([DEFAULT_POOL_KEY.to_string()])),
+            cache,
+        }
+    }
+
+    fn build_client(proxy: Option<&str>) -> Result<Client> {
+        let headers = HEADERS.clone();
+        let headers = (&headers).try_into()?;
+        let mut builder = Client::builder().default_headers(headers);
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+
+        Ok(builder.build()?)
+    }
+
+    fn build_chrome_pool(proxy: Option<&str>, extra_chrome_args: &[String]) -> Result<Pool<TabManager>> {
+        let signature = pool_signature(proxy, extra_chrome_args);
+        let user_data_dir = temp_dir().join(format!("alita-profile-{}", signature));
         debug!("Using user data dir: {:?}", user_data_dir);
         let sandbox = env::var("ALITA_DISABLE_SANDBOX").is_err();
-        let browser = Browser::new(
-            LaunchOptionsBuilder::default()
-                .headless(true)
-                .sandbox(sandbox)
-                .idle_browser_timeout(Duration::from_secs(31560000))
-                .user_data_dir(Some(user_data_dir.into()))
-                .build()
-                .unwrap(),
-        )
-        .unwrap();
 
-        let headers = HEADERS.clone();
-        let headers = (&headers).try_into().expect("Failed to convert headers");
-        let client = Client::builder().default_headers(headers).build().unwrap();
+        // chrome's --proxy-server flag silently drops userinfo, so strip it out before handing
+        // the url to the launch options and authenticate the resulting challenge ourselves.
+        let (proxy_server, proxy_credentials) = match proxy {
+            Some(proxy) => {
+                let (server, credentials) = split_proxy_credentials(proxy)?;
+                (Some(server), credentials)
+            }
+            None => (None, None),
+        };
+
+        let extra_args: Vec<&OsStr> = extra_chrome_args.iter().map(OsStr::new).collect();
+        let mut builder = LaunchOptionsBuilder::default();
+        builder
+            .headless(true)
+            .sandbox(sandbox)
+            .idle_browser_timeout(Duration::from_secs(31560000))
+            .user_data_dir(Some(user_data_dir.into()))
+            .args(extra_args)
+            .proxy_server(proxy_server.as_deref());
 
+        let browser = Browser::new(builder.build()?)?;
         let browser = Arc::new(browser);
         let max_size = env::var("ALITA_TAB_POOL_SIZE")
             .unwrap_or("10".to_string())
             .parse::<usize>()
             .expect("Failed to parse ALITA_TAB_POOL_SIZE");
 
-        let tabs = Pool::builder(TabManager { browser })
+        Ok(Pool::builder(TabManager::new(browser, proxy_credentials))
             .max_size(max_size)
-            .build()
-            .unwrap();
+            .build()?)
+    }
+
+    /// Returns the reqwest client for `req.proxy`, lazily building (and caching) one the first
+    /// time a given proxy is seen.
+    fn client_for(&self, req: &FetchRequest) -> Result<Client> {
+        let key = req.proxy.clone().unwrap_or_else(|| DEFAULT_POOL_KEY.to_string());
+        if let Some(client) = self.clients.get(&key) {
+            return Ok(client.clone());
+        }
+
+        let client = Self::build_client(req.proxy.as_deref())?;
+        self.clients.insert(key, client.clone());
+        Ok(client)
+    }
+
+    /// Returns the tab pool for `req`'s proxy/extra_chrome_args, lazily launching a dedicated
+    /// browser for that signature the first time it's seen. Evicts the least-recently-used pool
+    /// once more than `ALITA_MAX_CHROME_POOLS` are alive.
+    fn chrome_pool_for(&self, req: &FetchRequest) -> Result<Arc<Pool<TabManager>>> {
+        let key = pool_signature(req.proxy.as_deref(), &req.extra_chrome_args);
+        if let Some(pool) = self.chrome_pools.get(&key) {
+            let pool = pool.clone();
+            self.touch_pool(&key);
+            return Ok(pool);
+        }
+
+        let pool = Arc::new(Self::build_chrome_pool(req.proxy.as_deref(), &req.extra_chrome_args)?);
+        self.chrome_pools.insert(key.clone(), pool.clone());
+        self.touch_pool(&key);
+        self.evict_stale_pools();
+        Ok(pool)
+    }
+
+    fn touch_pool(&self, key: &str) {
+        let mut order = self.pool_order.lock().unwrap();
+        order.retain(|existing| existing != key);
+        order.push_back(key.to_string());
+    }
+
+    fn evict_stale_pools(&self) {
+        let max_pools = env::var("ALITA_MAX_CHROME_POOLS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_CHROME_POOLS);
+
+        // the default pool is never evicted, so it always counts towards the cap.
+        let max_pools = max_pools.max(1);
+        let mut order = self.pool_order.lock().unwrap();
+        while order.len() > max_pools {
+            let Some(stale_key) = order.pop_front() else {
+                break;
+            };
+
+            // the default pool always has to exist for requests with no proxy/extra args, so
+            // skip over it and evict the next least-recently-used pool instead.
+            if stale_key == DEFAULT_POOL_KEY {
+                order.push_back(stale_key);
+                continue;
+            }
+
+            debug!("Evicting idle chrome pool for signature {}", stale_key);
+            self.chrome_pools.remove(&stale_key);
+        }
+    }
 
-        Fetch { tabs, client }
+    /// Checks out a tab for `req`: a tab from the shared pool (its own isolated context, reset
+    /// between checkouts) normally, or a tab in `req.session_id`'s shared context when one is given, so
+    /// repeated calls for the same session keep the same cookies/storage.
+    async fn acquire_tab(&self, req: &FetchRequest) -> Result<TabHandle> {
+        let pool = self.chrome_pool_for(req)?;
+        match &req.session_id {
+            Some(session_id) => Ok(TabHandle::Session(pool.manager().tab_for_session(session_id)?)),
+            None => Ok(TabHandle::Pooled(pool.get().await.expect("Failed to get tab"))),
+        }
     }
 
     pub async fn get_html(&self, req: FetchRequest) -> Result<String> {
         info!("Fetching {:?}", &req);
+
+        if req.capture_network.unwrap_or(false) {
+            return self.capture_network(req).await;
+        }
+
+        // a session's html is personalized (logged-in state, consent choices, ...) and must
+        // never be shared with another caller of the same url, so skip the shared cache
+        // entirely rather than trust a cache key to keep sessions apart.
+        let cache = self.cache.as_ref().filter(|_| req.session_id.is_none());
+        let Some(cache) = cache else {
+            return self.fetch_uncached(req).await.map(|(html, _)| html);
+        };
+
+        let key = ResponseCache::key_for(&req);
+        if let Some(html) = self.serve_from_cache(cache, &key, &req).await? {
+            return Ok(html);
+        }
+
+        // don't let two inflight requests for the same url both hit the network/chrome; the
+        // loser waits here and then reads what the winner wrote.
+        let _claim = cache.claim(&key).await;
+        if let Some(html) = self.serve_from_cache(cache, &key, &req).await? {
+            return Ok(html);
+        }
+
+        let (html, headers) = self.fetch_uncached(req).await?;
+        if headers.cacheable {
+            cache
+                .store(&key, &CacheEntry::new(html.clone(), headers))
+                .await
+                .ok();
+        }
+        Ok(html)
+    }
+
+    /// Returns a usable cached body for `key`, revalidating with a conditional request first
+    /// if the entry is stale but carries an `ETag`/`Last-Modified`. Returns `None` if nothing
+    /// cached can be served and the caller should fall through to a real fetch.
+    async fn serve_from_cache(
+        &self,
+        cache: &ResponseCache,
+        key: &str,
+        req: &FetchRequest,
+    ) -> Result<Option<String>> {
+        let Some(entry) = cache.get(key).await else {
+            return Ok(None);
+        };
+
+        if cache.is_fresh(&entry) {
+            debug!("Serving {} from cache", &req.url);
+            return Ok(Some(entry.html));
+        }
+
+        if entry.etag.is_none() && entry.last_modified.is_none() {
+            return Ok(None);
+        }
+
+        debug!("Revalidating stale cache entry for {}", &req.url);
+        let mut request = self.client_for(req)?.get(&req.url);
+        if let Some(etag) = &entry.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &entry.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+
+        let res = request.send().await?;
+        if res.status() == StatusCode::NOT_MODIFIED {
+            let headers = CacheHeaders::from_response(&res);
+            cache.touch(key, &entry, headers).await.ok();
+            return Ok(Some(entry.html));
+        }
+
+        Ok(None)
+    }
+
+    /// The original, uncached fetch flow: try reqwest first, and fall back to chrome if the
+    /// response looks like a block page (requests with a `session_id` skip reqwest and go
+    /// straight to chrome, see below). Also returns the response's cache-relevant headers,
+    /// captured from the reqwest response (the chrome fallback doesn't have any to offer).
+    async fn fetch_uncached(&self, req: FetchRequest) -> Result<(String, CacheHeaders)> {
+        // reqwest's client is keyed only by proxy and carries no cookie jar, so a session's
+        // cookies/storage only exist in its chrome tab (see acquire_tab/tab_for_session). Going
+        // through reqwest first for a session request would silently drop that continuity on
+        // every page that doesn't trip is_blocked_elements, so send sessions straight to chrome.
+        if req.session_id.is_some() {
+            debug!("Session {:?} set, fetching from {} with chrome", &req.session_id, &req.url);
+            let html = self.fetch_with_chrome(req, None).await?;
+            return Ok((html, CacheHeaders::default()));
+        }
+
         let html = {
             debug!("Fetching html from {} with reqwest", &req.url);
-            let res = self.client.get(&req.url).send().await?.error_for_status()?;
+            let res = self.client_for(&req)?.get(&req.url).send().await?.error_for_status()?;
+            let headers = CacheHeaders::from_response(&res);
             let html = res.text().await?;
 
             // if the html contains any elements matching is_blocked_elements, we hit a block page and have to
@@ -101,26 +348,19 @@ impl Fetch {
                 // reuse the html we fetched with chrome
                 Some(html)
             } else {
-                return Ok(html);
+                return Ok((html, headers));
             }
         };
 
         debug!("Found blocked element, retrying with chrome");
-        self.fetch_with_chrome(req, html).await
+        let html = self.fetch_with_chrome(req, html).await?;
+        Ok((html, CacheHeaders::default()))
     }
 
     async fn fetch_with_chrome(&self, req: FetchRequest, html: Option<String>) -> Result<String> {
         debug!("Fetching html from {} with chrome", &req.url);
-        let tab = self.tabs.get().await.expect("Failed to get tab");
-        self.configure_interceptor(&tab, html)?;
-        tab.navigate_to(&req.url)?;
-        if let Some(wait_for_element) = &req.wait_for_element {
-            let wait_timeout = req.wait_timeout.unwrap_or(20);
-            let wait_timeout = Duration::from_secs(wait_timeout as u64);
-            tab.wait_for_element_with_custom_timeout(&wait_for_element, wait_timeout)?;
-        } else {
-            tab.wait_until_navigated()?;
-        }
+        let tab = self.acquire_tab(&req).await?;
+        self.navigate(&tab, &req, html, None, default_block_resource_types())?;
 
         let html = tab.get_content()?;
 
@@ -139,27 +379,196 @@ impl Fetch {
         }
     }
 
-    fn configure_interceptor(&self, tab: &Arc<Tab>, html: Option<String>) -> Result<()> {
+    /// Navigates the page and returns every request/response the interceptor observed as a
+    /// JSON document instead of the rendered HTML, so callers can harvest XHR/fetch traffic
+    /// (e.g. the JSON endpoints a SPA calls) without scraping the DOM.
+    async fn capture_network(&self, req: FetchRequest) -> Result<String> {
+        debug!("Capturing network traffic for {}", &req.url);
+        let tab = self.acquire_tab(&req).await?;
+        let capture = NetworkCapture::new(req.capture_resource_types.clone());
+        self.navigate(&tab, &req, None, Some(capture.clone()), default_block_resource_types())?;
+        tab.navigate_to("about:blank")?;
+
+        // narrow the Fetch domain back down to the Request-only baseline now that this call's
+        // done with it - pooled tabs also get this via reset_tab on recycle, but session tabs
+        // are never recycled, so do it here too rather than leaving every later call on that
+        // session paying for the Response stage it doesn't need.
+        tab.disable_fetch()?;
+        tab.enable_fetch(Some(REQUEST_STAGE_ONLY.as_slice()), None)?;
+
+        Ok(serde_json::to_string(&capture.into_exchanges())?)
+    }
+
+    pub async fn get_screenshot(&self, req: FetchRequest) -> Result<Vec<u8>> {
+        info!("Screenshotting {:?}", &req);
+        let tab = self.acquire_tab(&req).await?;
+        // unlike the html fetch path, don't block images/stylesheets/etc by default here - a
+        // screenshot with no images or styling defeats the point of the endpoint. callers that
+        // want the old blocking behaviour can still set `block_resource_types` themselves.
+        self.navigate(&tab, &req, None, None, Vec::new())?;
+
+        let format = match req.format.as_deref() {
+            Some("jpeg") => CaptureScreenshotFormatOption::Jpeg,
+            _ => CaptureScreenshotFormatOption::Png,
+        };
+        let clip = match req.clip.as_ref() {
+            Some(clip) => Some(Viewport {
+                x: clip.x,
+                y: clip.y,
+                width: clip.width,
+                height: clip.height,
+                scale: clip.scale,
+            }),
+            // an explicit clip always wins; otherwise expand to the full scrollable page when
+            // asked for one, since `from_surface` alone doesn't change the capture region.
+            None if req.full_page.unwrap_or(false) => Some(self.full_page_viewport(&tab)?),
+            None => None,
+        };
+        let data = tab.capture_screenshot(format, req.quality, clip, true)?;
+
+        // park the tab like we do after a regular fetch, see the comment in fetch_with_chrome.
+        tab.navigate_to("about:blank")?;
+
+        Ok(data)
+    }
+
+    /// Returns a viewport covering the full scrollable page, for `full_page` screenshots.
+    fn full_page_viewport(&self, tab: &Arc<Tab>) -> Result<Viewport> {
+        let metrics = tab.call_method(GetLayoutMetrics {})?;
+        let content_size = metrics.css_content_size;
+        Ok(Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: content_size.width,
+            height: content_size.height,
+            scale: 1.0,
+        })
+    }
+
+    pub async fn get_pdf(&self, req: FetchRequest) -> Result<Vec<u8>> {
+        info!("Printing {:?} to pdf", &req);
+        let tab = self.acquire_tab(&req).await?;
+        // see get_screenshot: don't block images/stylesheets by default for a rendered pdf either.
+        self.navigate(&tab, &req, None, None, Vec::new())?;
+
+        let data = tab.print_to_pdf(None::<PrintToPdfOptions>)?;
+        tab.navigate_to("about:blank")?;
+
+        Ok(data)
+    }
+
+    /// Navigates `tab` to `req.url` and waits for it to be ready, reusing the same
+    /// interception/wait-for-element behaviour used by `fetch_with_chrome`. It does not check
+    /// the page against `is_blocked_elements`: `fetch_with_chrome` does that itself afterwards,
+    /// while `/screenshot` and `/pdf` render whatever page loads. `default_blocked` is
+    /// what applies when the caller doesn't set `block_resource_types` explicitly - it differs
+    /// by endpoint, since blocking images/stylesheets is great for scraping html but ruins a
+    /// screenshot or pdf.
+    fn navigate(
+        &self,
+        tab: &Arc<Tab>,
+        req: &FetchRequest,
+        html: Option<String>,
+        capture: Option<NetworkCapture>,
+        default_blocked: Vec<String>,
+    ) -> Result<()> {
+        let block_resource_types = req.block_resource_types.clone().unwrap_or(default_blocked);
+        self.configure_interceptor(tab, html, capture, block_resource_types)?;
+        tab.navigate_to(&req.url)?;
+        if let Some(wait_for_element) = &req.wait_for_element {
+            let wait_timeout = req.wait_timeout.unwrap_or(20);
+            let wait_timeout = Duration::from_secs(wait_timeout as u64);
+            tab.wait_for_element_with_custom_timeout(&wait_for_element, wait_timeout)?;
+        } else {
+            tab.wait_until_navigated()?;
+        }
+
+        Ok(())
+    }
+
+    fn configure_interceptor(
+        &self,
+        tab: &Arc<Tab>,
+        html: Option<String>,
+        capture: Option<NetworkCapture>,
+        block_resource_types: Vec<String>,
+    ) -> Result<()> {
+        // every tab is checked out paused at the Request stage only (see tab_manager.rs); a
+        // capture_network call additionally needs the Response stage to see status/headers/
+        // bodies, so widen to both stages just for this call instead of every call everywhere
+        // paying for a CDP round-trip it doesn't use. capture_network narrows this back down
+        // once it's done, see the end of that function.
+        if capture.is_some() {
+            tab.enable_fetch(Some(BOTH_STAGES.as_slice()), None)?;
+        }
+
         // this configures the interception handler
         let used_html = AtomicBool::new(false);
         let html = Arc::new(html);
         tab.enable_request_interception(Arc::new(
-            move |_t: Arc<Transport>, _sid: SessionId, intercepted: RequestPausedEvent| {
+            move |t: Arc<Transport>, sid: SessionId, intercepted: RequestPausedEvent| {
+                // a capture_network call widens the tab to pause at the Response stage too (see
+                // above) so we can record response status/headers; a response_status_code means
+                // we're seeing the response half of a request we already let through.
+                if intercepted.params.response_status_code.is_some() {
+                    if let Some(capture) = &capture {
+                        let headers = intercepted
+                            .params
+                            .response_headers
+                            .as_ref()
+                            .map(|headers| header_entries_to_map(headers));
+                        // best-effort: the body isn't available for e.g. redirects or requests
+                        // that were aborted, so a failure here just means no body is recorded.
+                        let body = t
+                            .call_method_on_target(
+                                sid,
+                                GetResponseBody {
+                                    request_id: intercepted.params.request_id.clone(),
+                                },
+                            )
+                            .ok();
+                        capture.record_response(
+                            &intercepted.params.request_id,
+                            intercepted.params.response_status_code,
+                            headers,
+                            body.as_ref().map(|b| b.body.clone()),
+                            body.map(|b| b.base_64_encoded),
+                        );
+                    }
+                    return RequestPausedDecision::Continue(None);
+                }
+
                 let is_ico = intercepted.params.request.url.ends_with(".ico");
                 if is_ico {
                     return RequestPausedDecision::Continue(None);
                 }
 
-                match intercepted.params.resource_Type {
-                    ResourceType::Image
-                    | ResourceType::Stylesheet
-                    | ResourceType::Media
-                    | ResourceType::Font
-                    | ResourceType::Ping
-                    | ResourceType::Manifest => RequestPausedDecision::Fail(FailRequest {
+                let resource_type = intercepted.params.resource_Type;
+                let resource_type_name = format!("{:?}", resource_type);
+                if let Some(capture) = &capture {
+                    if capture.wants(&resource_type_name) {
+                        capture.record_request(
+                            intercepted.params.request_id.clone(),
+                            intercepted.params.request.url.clone(),
+                            intercepted.params.request.method.clone(),
+                            resource_type_name.clone(),
+                            intercepted.params.request.headers.clone(),
+                        );
+                    }
+                }
+
+                if !matches!(resource_type, ResourceType::Document)
+                    && block_resource_types
+                        .iter()
+                        .any(|t| t.eq_ignore_ascii_case(&resource_type_name))
+                {
+                    return RequestPausedDecision::Fail(FailRequest {
                         request_id: intercepted.params.request_id,
                         error_reason: ErrorReason::Aborted,
-                    }),
+                    });
+                }
+
+                match resource_type {
                     ResourceType::Document => {
                         if let Some(html) = html.as_ref() {
                             if !used_html.load(Ordering::Relaxed) {
@@ -204,3 +613,37 @@ impl Fetch {
         false
     }
 }
+
+/// The resource types blocked when a request doesn't set `block_resource_types` itself -
+/// the set we've always blocked, kept as the default now that it's configurable.
+fn default_block_resource_types() -> Vec<String> {
+    vec!["Image", "Stylesheet", "Media", "Font", "Ping", "Manifest"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+/// Splits `user:pass` out of a proxy url, returning the url without credentials (safe to pass
+/// to Chrome's `--proxy-server`, which doesn't accept userinfo) and the credentials separately
+/// so they can be supplied via the proxy's auth challenge instead.
+fn split_proxy_credentials(proxy: &str) -> Result<(String, Option<(String, String)>)> {
+    let mut url = reqwest::Url::parse(proxy)?;
+    if url.username().is_empty() && url.password().is_none() {
+        return Ok((proxy.to_string(), None));
+    }
+
+    let credentials = (url.username().to_string(), url.password().unwrap_or("").to_string());
+    url.set_username("").ok();
+    url.set_password(None).ok();
+    Ok((url.to_string(), Some(credentials)))
+}
+
+/// A stable key for the browser+pool that should handle a given proxy/extra-args combination,
+/// so requests with identical launch options share a browser instead of each spawning one.
+fn pool_signature(proxy: Option<&str>, extra_chrome_args: &[String]) -> String {
+    if proxy.is_none() && extra_chrome_args.is_empty() {
+        return DEFAULT_POOL_KEY.to_string();
+    }
+
+    format!("{}|{}", proxy.unwrap_or(""), extra_chrome_args.join(" "))
+}
@@ -1,5 +1,6 @@
 use alita::request::FetchRequest;
 use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
 use axum::response::IntoResponse;
 use axum::routing::{get, post};
 use axum::{Json, Router};
@@ -13,8 +14,10 @@ use tokio::signal;
 use tower_http::compression::CompressionLayer;
 use tracing::info;
 
+mod cache;
 mod error;
 mod fetch;
+mod network_capture;
 mod protocol;
 mod tab_manager;
 
@@ -28,6 +31,10 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let app = Router::new()
         .route("/", get(get_url))
         .route("/", post(post_url))
+        .route("/screenshot", get(get_screenshot))
+        .route("/screenshot", post(post_screenshot))
+        .route("/pdf", get(get_pdf))
+        .route("/pdf", post(post_pdf))
         .layer(compression_layer)
         .with_state(fetch);
 
@@ -62,6 +69,51 @@ async fn post_url(
     Ok(result)
 }
 
+#[axum::debug_handler]
+async fn get_screenshot(
+    State(fetch): State<Arc<Fetch>>,
+    Query(query): Query<FetchRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let content_type = screenshot_content_type(&query);
+    let image = fetch.get_screenshot(query).await?;
+    Ok(([(CONTENT_TYPE, content_type)], image))
+}
+
+#[axum::debug_handler]
+async fn post_screenshot(
+    State(fetch): State<Arc<Fetch>>,
+    Json(body): Json<FetchRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let content_type = screenshot_content_type(&body);
+    let image = fetch.get_screenshot(body).await?;
+    Ok(([(CONTENT_TYPE, content_type)], image))
+}
+
+#[axum::debug_handler]
+async fn get_pdf(
+    State(fetch): State<Arc<Fetch>>,
+    Query(query): Query<FetchRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let pdf = fetch.get_pdf(query).await?;
+    Ok(([(CONTENT_TYPE, "application/pdf")], pdf))
+}
+
+#[axum::debug_handler]
+async fn post_pdf(
+    State(fetch): State<Arc<Fetch>>,
+    Json(body): Json<FetchRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let pdf = fetch.get_pdf(body).await?;
+    Ok(([(CONTENT_TYPE, "application/pdf")], pdf))
+}
+
+fn screenshot_content_type(req: &FetchRequest) -> &'static str {
+    match req.format.as_deref() {
+        Some("jpeg") => "image/jpeg",
+        _ => "image/png",
+    }
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
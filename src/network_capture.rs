@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use headless_chrome::protocol::cdp::Fetch::HeaderEntry;
+use serde::Serialize;
+
+/// A single request/response pair observed by the interceptor while `capture_network` is set,
+/// surfaced to callers instead of the rendered HTML.
+#[derive(Debug, Clone, Serialize)]
+pub struct CapturedExchange {
+    pub url: String,
+    pub method: String,
+    pub resource_type: String,
+    pub request_headers: HashMap<String, String>,
+    pub response_status: Option<i64>,
+    pub response_headers: Option<HashMap<String, String>>,
+    /// The response body fetched via `Fetch.getResponseBody`, so XHR/fetch payloads (e.g. a
+    /// SPA's JSON endpoints) can actually be harvested, not just their metadata.
+    pub response_body: Option<String>,
+    /// Whether `response_body` is base64-encoded, per `Fetch.getResponseBody` - true for binary
+    /// bodies, false for text ones.
+    pub response_body_base64_encoded: Option<bool>,
+}
+
+/// Collects the request/response pairs the Fetch domain interceptor sees for a single
+/// `get_html` call. Cheap to clone: the underlying map is shared behind an `Arc` so it can be
+/// moved into the `'static` interception closure and still be read back afterwards.
+#[derive(Clone)]
+pub struct NetworkCapture {
+    resource_types: Option<Vec<String>>,
+    exchanges: Arc<DashMap<String, CapturedExchange>>,
+}
+
+impl NetworkCapture {
+    pub fn new(resource_types: Option<Vec<String>>) -> Self {
+        NetworkCapture {
+            resource_types,
+            exchanges: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// Whether a request of `resource_type` (e.g. `"XHR"`, `"Fetch"`, `"Document"`) should be
+    /// recorded, given the caller's `capture_resource_types` filter (no filter means capture
+    /// everything).
+    pub fn wants(&self, resource_type: &str) -> bool {
+        match &self.resource_types {
+            Some(types) => types.iter().any(|t| t.eq_ignore_ascii_case(resource_type)),
+            None => true,
+        }
+    }
+
+    pub fn record_request(
+        &self,
+        request_id: String,
+        url: String,
+        method: String,
+        resource_type: String,
+        headers: HashMap<String, String>,
+    ) {
+        self.exchanges.insert(
+            request_id,
+            CapturedExchange {
+                url,
+                method,
+                resource_type,
+                request_headers: headers,
+                response_status: None,
+                response_headers: None,
+                response_body: None,
+                response_body_base64_encoded: None,
+            },
+        );
+    }
+
+    /// Fills in the response side of a previously-recorded request. A miss means the request
+    /// was never recorded (e.g. it didn't match `capture_resource_types`), so it's ignored.
+    pub fn record_response(
+        &self,
+        request_id: &str,
+        status: Option<i64>,
+        headers: Option<HashMap<String, String>>,
+        body: Option<String>,
+        body_base64_encoded: Option<bool>,
+    ) {
+        if let Some(mut exchange) = self.exchanges.get_mut(request_id) {
+            exchange.response_status = status;
+            exchange.response_headers = headers;
+            exchange.response_body = body;
+            exchange.response_body_base64_encoded = body_base64_encoded;
+        }
+    }
+
+    pub fn into_exchanges(self) -> Vec<CapturedExchange> {
+        self.exchanges.iter().map(|e| e.value().clone()).collect()
+    }
+}
+
+pub fn header_entries_to_map(entries: &[HeaderEntry]) -> HashMap<String, String> {
+    entries
+        .iter()
+        .map(|entry| (entry.name.clone(), entry.value.clone()))
+        .collect()
+}
@@ -1,22 +1,141 @@
 use crate::fetch::HEADERS;
 use crate::protocol::protocol::cdp::Fetch::{RequestPattern, RequestStage};
+use crate::protocol::protocol::cdp::Network::{ClearBrowserCache, ClearBrowserCookies};
+use crate::protocol::protocol::cdp::Storage::ClearDataForOrigin;
+use crate::protocol::protocol::cdp::Target::{
+    BrowserContextId, CreateBrowserContext, CreateTarget, DisposeBrowserContext,
+};
 use anyhow::Result;
-use deadpool::managed::{Manager, RecycleResult};
+use dashmap::DashMap;
+use deadpool::managed::{Manager, Object, RecycleError, RecycleResult};
 use headless_chrome::Browser;
 use headless_chrome::Tab;
-use std::sync::Arc;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+// each session pins its own tab (and browser context) alive for the process lifetime, so cap
+// how many distinct session_ids can be alive at once.
+const DEFAULT_MAX_SESSIONS: usize = 32;
+
+lazy_static::lazy_static! {
+    // the Fetch-domain pattern every tab starts (and is reset to) with - only the Request stage,
+    // since that's all a plain fetch/screenshot/pdf needs. capture_network calls widen this to
+    // include the Response stage for their own duration; see configure_interceptor in fetch.rs.
+    pub(crate) static ref REQUEST_STAGE_ONLY: Vec<RequestPattern> = vec![RequestPattern {
+        url_pattern: None,
+        resource_Type: None,
+        request_stage: Some(RequestStage::Request),
+    }];
+}
 
 pub struct TabManager {
     pub browser: Arc<Browser>,
+    // maps a tab's target id to the browser context it lives in, so recycle can tear that
+    // context down when it drops a tab that failed its health check.
+    contexts: DashMap<String, BrowserContextId>,
+    // maps a caller-supplied session_id to its browser context and tab, so repeated fetches for
+    // the same session share a cookie jar/local storage (and don't leak a new tab every call).
+    sessions: Mutex<HashMap<String, (BrowserContextId, Arc<Tab>)>>,
+    // tracks sessions' keys in least-to-most-recently-used order, so we know which session to
+    // evict when we're over the cap.
+    session_order: Mutex<VecDeque<String>>,
+    // username/password for this pool's proxy, stripped out of the --proxy-server url (chrome
+    // ignores userinfo there) and supplied via the proxy's auth challenge instead.
+    proxy_credentials: Option<(String, String)>,
 }
 
-// todo: this is kinda mid, a more appropriate pool would be better
-impl Manager for TabManager {
-    type Type = Arc<Tab>;
-    type Error = anyhow::Error;
+impl TabManager {
+    pub fn new(browser: Arc<Browser>, proxy_credentials: Option<(String, String)>) -> Self {
+        TabManager {
+            browser,
+            contexts: DashMap::new(),
+            sessions: Mutex::new(HashMap::new()),
+            session_order: Mutex::new(VecDeque::new()),
+            proxy_credentials,
+        }
+    }
 
-    async fn create(&self) -> Result<Self::Type, Self::Error> {
-        let tab = self.browser.new_tab()?;
+    /// Returns the tab for `session_id`, creating its browser context and tab the first time the
+    /// session is seen and handing back the same tab on every later call. Reusing one tab (rather
+    /// than spawning a new one per call) keeps a long-lived session from accumulating orphaned
+    /// tabs in the browser, since nothing else closes them. Evicts the least-recently-used
+    /// session once more than `ALITA_MAX_SESSIONS` are alive.
+    pub fn tab_for_session(&self, session_id: &str) -> Result<Arc<Tab>> {
+        {
+            let sessions = self.sessions.lock().unwrap();
+            if let Some((_, tab)) = sessions.get(session_id) {
+                let tab = tab.clone();
+                drop(sessions);
+                self.touch_session(session_id);
+                return Ok(tab);
+            }
+        }
+
+        debug!("Creating isolated browser context for session {}", session_id);
+        let context_id = self.create_context()?;
+        let tab = self.spawn_tab(&context_id)?;
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(session_id.to_string(), (context_id, tab.clone()));
+        self.touch_session(session_id);
+        self.evict_stale_sessions();
+        Ok(tab)
+    }
+
+    fn touch_session(&self, session_id: &str) {
+        let mut order = self.session_order.lock().unwrap();
+        order.retain(|existing| existing != session_id);
+        order.push_back(session_id.to_string());
+    }
+
+    fn evict_stale_sessions(&self) {
+        let max_sessions = env::var("ALITA_MAX_SESSIONS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_SESSIONS);
+
+        let mut order = self.session_order.lock().unwrap();
+        while order.len() > max_sessions {
+            let Some(stale_id) = order.pop_front() else {
+                break;
+            };
+
+            let evicted = self.sessions.lock().unwrap().remove(&stale_id);
+            if let Some((context_id, _tab)) = evicted {
+                debug!("Evicting idle session {}", stale_id);
+                let _ = self.browser.call_method(DisposeBrowserContext {
+                    browser_context_id: context_id,
+                });
+            }
+        }
+    }
+
+    fn create_context(&self) -> Result<BrowserContextId> {
+        let context = self.browser.call_method(CreateBrowserContext {
+            dispose_on_detach: None,
+            proxy_server: None,
+            proxy_bypass_list: None,
+            origins_with_universal_network_access: None,
+        })?;
+
+        Ok(context.browser_context_id)
+    }
+
+    fn spawn_tab(&self, context_id: &BrowserContextId) -> Result<Arc<Tab>> {
+        let tab = self.browser.new_tab_with_options(CreateTarget {
+            url: "about:blank".to_string(),
+            width: None,
+            height: None,
+            browser_context_id: Some(context_id.clone()),
+            enable_begin_frame_control: None,
+            new_window: None,
+            background: None,
+            for_tab: None,
+        })?;
 
         tab.enable_stealth_mode()?;
 
@@ -28,27 +147,114 @@ impl Manager for TabManager {
             .expect("User-Agent header not found");
         tab.set_user_agent(&user_agent, None, None)?;
 
-        // this tells chrome we want to intercept requests, it starts sending us requests
-        tab.enable_fetch(
-            Some(
-                vec![RequestPattern {
-                    url_pattern: None,
-                    resource_Type: None,
-                    request_stage: Some(RequestStage::Request),
-                }]
-                .as_slice(),
-            ),
-            None,
-        )?;
+        if let Some((username, password)) = &self.proxy_credentials {
+            tab.authenticate(Some(username.clone()), Some(password.clone()))?;
+        }
+
+        // this tells chrome we want to intercept requests, it starts sending us requests. only
+        // the Request stage is registered here - that's all every fetch/screenshot/pdf needs
+        // (blocking resources, fulfilling with cached html). capture_network additionally needs
+        // the Response stage to see status/headers/bodies, so fetch.rs's configure_interceptor
+        // re-registers both stages for the duration of that call, rather than every request on
+        // every call pausing twice at the CDP level for a feature most calls don't use.
+        tab.enable_fetch(Some(REQUEST_STAGE_ONLY.as_slice()), None)?;
+
+        Ok(tab)
+    }
+
+    /// Resets a tab's state between checkouts so a caller never sees another caller's cookies,
+    /// cache, local/session storage, or stale interception handler. Returns `Err` if the tab no
+    /// longer responds, in which case it's not safe to hand back out and should be dropped
+    /// instead.
+    fn reset_tab(&self, tab: &Arc<Tab>) -> Result<()> {
+        tab.call_method(ClearBrowserCookies {})?;
+        tab.call_method(ClearBrowserCache {})?;
+        // clearDataForOrigin needs an origin to scope to; if the tab never navigated anywhere
+        // (or is already parked on about:blank) there's nothing origin-scoped to clear.
+        if let Some(origin) = tab.get_url().ok().as_deref().and_then(origin_of) {
+            tab.call_method(ClearDataForOrigin {
+                origin,
+                storage_types: "all".to_string(),
+            })?;
+        }
+        tab.disable_fetch()?;
+        // back to the Request-only baseline - if the previous checkout was a capture_network
+        // call that widened this to both stages, that widening shouldn't outlive it.
+        tab.enable_fetch(Some(REQUEST_STAGE_ONLY.as_slice()), None)?;
+
+        // a trivial round trip through the page's js context to confirm the tab is still alive
+        // and not, say, crashed or wedged on a dialog.
+        tab.evaluate("1", false)?;
+
+        Ok(())
+    }
+
+    fn dispose_context_for(&self, tab: &Arc<Tab>) {
+        if let Some((_, context_id)) = self.contexts.remove(&format!("{:?}", tab.get_target_id())) {
+            let _ = self.browser.call_method(DisposeBrowserContext {
+                browser_context_id: context_id,
+            });
+        }
+    }
+}
+
+impl Manager for TabManager {
+    type Type = Arc<Tab>;
+    type Error = anyhow::Error;
+
+    async fn create(&self) -> Result<Self::Type, Self::Error> {
+        // every pooled tab gets its own incognito-style browser context so cookies/local
+        // storage from one caller's fetch can never leak into an unrelated one.
+        let context_id = self.create_context()?;
+        let tab = self.spawn_tab(&context_id)?;
+        self.contexts
+            .insert(format!("{:?}", tab.get_target_id()), context_id);
 
         Ok(tab)
     }
 
     async fn recycle(
         &self,
-        _tab: &mut Arc<Tab>,
+        tab: &mut Arc<Tab>,
         _metrics: &deadpool::managed::Metrics,
     ) -> RecycleResult<Self::Error> {
+        if self.reset_tab(tab).is_err() {
+            self.dispose_context_for(tab);
+            return Err(RecycleError::Message(
+                "tab failed its post-use health check, dropping it".into(),
+            ));
+        }
+
         Ok(())
     }
 }
+
+/// A checked-out tab, either from the shared pool (isolated context, reset between checkouts) or from a
+/// caller's named session (shared context, reused across calls). Derefs to `Arc<Tab>` so call
+/// sites don't need to care which kind they have.
+pub enum TabHandle {
+    Pooled(Object<TabManager>),
+    Session(Arc<Tab>),
+}
+
+/// Extracts `scheme://host[:port]` from a url, for scoping `Storage.clearDataForOrigin` calls.
+fn origin_of(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    if authority.is_empty() {
+        return None;
+    }
+
+    Some(format!("{}://{}", scheme, authority))
+}
+
+impl Deref for TabHandle {
+    type Target = Arc<Tab>;
+
+    fn deref(&self) -> &Arc<Tab> {
+        match self {
+            TabHandle::Pooled(tab) => &*tab,
+            TabHandle::Session(tab) => tab,
+        }
+    }
+}